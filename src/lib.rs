@@ -19,7 +19,7 @@
 // This library uses a simple `nop` loop in assembly to wait.
 //
 // WS2812s read inputs with cycles about 333ns long.
-// Write 3 bytes (GRB | Green, Red, Blue) _**per LED**_, then "latch" (set low) for 6us to 250us depending on your model.
+// Write 3 bytes (GRB | Green, Red, Blue by default, see `ColorOrder`) _**per LED**_, then "latch" (set low) for 6us to 280us depending on your model.
 // Writing a single bit entails a three-bit message that looks like `[1, x, 0]`, where x is the bit you want to write.
 // That all is to say, to write a bit of `1` to an LED you must send `[1, 1, 0]`, waiting 333ns between each bit.
 //
@@ -28,7 +28,9 @@
 //
 // Find out more about the [timing constraints of WS2812s](https://wp.josh.com/2014/05/13/ws2812-neopixels-are-not-so-finicky-once-you-get-to-know-them/).
 //
-// P.S. I have the latch hard coded to 6us. If you need 250us shoot me an email or clone the project.
+// P.S. The bit and latch timings are configurable via `Timing`, with presets for
+// a few common chips (`Timing::ws2812()`, `Timing::ws2815()`, `Timing::sk6812()`).
+// `Ws2812::new` defaults to WS2812 timings; use `Ws2812::new_with_timing` for anything else.
 //
 // ## Example using teensy4-bsp
 //
@@ -81,7 +83,10 @@
 use core::arch::asm;
 use embedded_hal::digital::v2::OutputPin;
 
-use smart_leds_trait::{SmartLedsWrite, RGB8};
+#[cfg(feature = "calibrate")]
+use cortex_m::peripheral::DWT;
+
+use smart_leds_trait::{SmartLedsWrite, RGB8, RGBW};
 
 const CYCLES_PER_LOOP: f32 = 3.0;
 
@@ -89,9 +94,144 @@ const fn n_loops_at(ns: f32, mhz: f32) -> i32 {
     (ns / ((1000.0 / mhz) * CYCLES_PER_LOOP)) as i32
 }
 
+/// Busy-wait for (ideally) `loops` * `CYCLES_PER_LOOP` core clock cycles.
+#[inline(always)]
+fn nop_wait(loops: i32) {
+    unsafe {
+        asm!(
+            "mov     r2, {0}",
+
+            "2:",
+                "nop",
+                "nop",
+                "subs     r2, 1",
+                "cmp      r2, 0",
+                "bne      2b",
+
+            in(reg) loops
+        )
+    }
+}
+
+/// Pulse widths (in nanoseconds) for a one-wire RGB LED protocol.
+///
+/// The WS2812 family shares a single bit-bang protocol, but individual
+/// chips (and their clones) disagree on the exact high/low times for a `0`
+/// or `1` bit, and on how long the latch/reset period needs to be. Build a
+/// `Timing` from one of the presets below, or construct one directly for a
+/// chip that isn't covered yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    /// High time of a `0` bit, in nanoseconds.
+    pub t0h_ns: f32,
+    /// Low time of a `0` bit, in nanoseconds.
+    pub t0l_ns: f32,
+    /// High time of a `1` bit, in nanoseconds.
+    pub t1h_ns: f32,
+    /// Low time of a `1` bit, in nanoseconds.
+    pub t1l_ns: f32,
+    /// Latch/reset time after the last bit, in nanoseconds.
+    pub reset_ns: f32,
+}
+
+impl Timing {
+    /// Timings for the classic WS2812 (and WS2812B).
+    pub const fn ws2812() -> Self {
+        Self {
+            t0h_ns: 300.0,
+            t0l_ns: 666.0,
+            t1h_ns: 700.0,
+            t1l_ns: 350.0,
+            reset_ns: 6000.0,
+        }
+    }
+
+    /// Timings for the WS2815, which wants noticeably shorter high times and
+    /// a much longer latch than a WS2812.
+    pub const fn ws2815() -> Self {
+        Self {
+            t0h_ns: 220.0,
+            t0l_ns: 580.0,
+            t1h_ns: 580.0,
+            t1l_ns: 220.0,
+            reset_ns: 280_000.0,
+        }
+    }
+
+    /// Timings for the SK6812(RGBW), which is close to but not identical to
+    /// the WS2812.
+    pub const fn sk6812() -> Self {
+        Self {
+            t0h_ns: 300.0,
+            t0l_ns: 900.0,
+            t1h_ns: 600.0,
+            t1l_ns: 600.0,
+            reset_ns: 80_000.0,
+        }
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self::ws2812()
+    }
+}
+
+/// The order in which a strip wants its three color bytes.
+///
+/// WS2812s want G, R, B, but WS2811/WS2815 and various clones are wired for
+/// other orders. `Ws2812::write` and `Ws2812::write_rgbw` reorder the color
+/// channels of `RGB8`/`RGBW` before sending, so
+/// callers never have to pre-swizzle their color data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorOrder {
+    Grb,
+    Rgb,
+    Brg,
+    Bgr,
+    Rbg,
+    Gbr,
+}
+
+impl ColorOrder {
+    /// Reorder a color's three channels into the wire order this variant names.
+    fn reorder(self, color: RGB8) -> (u8, u8, u8) {
+        match self {
+            ColorOrder::Grb => (color.g, color.r, color.b),
+            ColorOrder::Rgb => (color.r, color.g, color.b),
+            ColorOrder::Brg => (color.b, color.r, color.g),
+            ColorOrder::Bgr => (color.b, color.g, color.r),
+            ColorOrder::Rbg => (color.r, color.b, color.g),
+            ColorOrder::Gbr => (color.g, color.b, color.r),
+        }
+    }
+}
+
+impl Default for ColorOrder {
+    /// WS2812s (the chip this crate is named for) want G, R, B.
+    fn default() -> Self {
+        ColorOrder::Grb
+    }
+}
+
+/// Pre-computed, calibration-corrected loop counts for the four pulse widths.
+///
+/// Set by [`Ws2812::calibrate`] once the fixed cost of toggling `PIN` is
+/// known, so `write_bit` no longer has to assume the `nop` loop is the only
+/// time spent per pulse.
+struct CalibratedLoops {
+    t0h: i32,
+    t0l: i32,
+    t1h: i32,
+    t1l: i32,
+}
+
 pub struct Ws2812<PIN> {
     pub pin: PIN,
     pub frequency_mhz: f32,
+    pub timing: Timing,
+    pub color_order: ColorOrder,
+    calibrated: Option<CalibratedLoops>,
 }
 
 impl<PIN> Ws2812<PIN>
@@ -99,42 +239,59 @@ where
     PIN: OutputPin,
 {
     /// The timer has to already run at with a frequency of 3 MHz
-    pub fn new(mut pin: PIN, frequency_mhz: f32) -> Ws2812<PIN> {
+    ///
+    /// Uses `Timing::default()` (WS2812 timings). To drive a different
+    /// chipset, use [`Ws2812::new_with_timing`].
+    pub fn new(pin: PIN, frequency_mhz: f32) -> Ws2812<PIN> {
+        Self::new_with_timing(pin, frequency_mhz, Timing::default())
+    }
+
+    /// Like [`Ws2812::new`], but with an explicit [`Timing`] for chips other
+    /// than the WS2812 (e.g. `Timing::ws2815()` or `Timing::sk6812()`).
+    ///
+    /// Uses `ColorOrder::default()` (GRB); chain [`Ws2812::with_color_order`]
+    /// for strips wired differently.
+    pub fn new_with_timing(mut pin: PIN, frequency_mhz: f32, timing: Timing) -> Ws2812<PIN> {
         pin.set_low().ok();
-        Self { pin, frequency_mhz }
+        Self {
+            pin,
+            frequency_mhz,
+            timing,
+            color_order: ColorOrder::default(),
+            calibrated: None,
+        }
+    }
+
+    /// Set the wire color order, for strips that aren't GRB.
+    pub fn with_color_order(mut self, color_order: ColorOrder) -> Self {
+        self.color_order = color_order;
+        self
     }
 
     /// Wait for (ideally) 333ns
     #[inline(always)]
     pub fn wait(&self, loops: i32) {
-        unsafe {
-            asm!(
-                "mov     r2, {0}",
-
-                "2:",
-                    "nop",
-                    "nop",
-                    "subs     r2, 1",
-                    "cmp      r2, 0",
-                    "bne      2b",
-
-                in(reg) loops
-            )
-        }
+        nop_wait(loops)
     }
 
     fn write_bit(&mut self, bit: bool) {
-        if bit {
-            self.pin.set_high().ok();
-            self.wait(n_loops_at(700.0, self.frequency_mhz));
-            self.pin.set_low().ok();
-            self.wait(n_loops_at(350.0, self.frequency_mhz));
-        } else {
-            self.pin.set_high().ok();
-            self.wait(n_loops_at(300.0, self.frequency_mhz));
-            self.pin.set_low().ok();
-            self.wait(n_loops_at(666.0, self.frequency_mhz));
-        }
+        let (high_loops, low_loops) = match (&self.calibrated, bit) {
+            (Some(c), true) => (c.t1h, c.t1l),
+            (Some(c), false) => (c.t0h, c.t0l),
+            (None, true) => (
+                n_loops_at(self.timing.t1h_ns, self.frequency_mhz),
+                n_loops_at(self.timing.t1l_ns, self.frequency_mhz),
+            ),
+            (None, false) => (
+                n_loops_at(self.timing.t0h_ns, self.frequency_mhz),
+                n_loops_at(self.timing.t0l_ns, self.frequency_mhz),
+            ),
+        };
+
+        self.pin.set_high().ok();
+        self.wait(high_loops);
+        self.pin.set_low().ok();
+        self.wait(low_loops);
     }
 
     fn write_byte(&mut self, mut data: u8) {
@@ -143,6 +300,35 @@ where
             data <<= 1;
         }
     }
+
+    /// Write all the items of an iterator to a SK6812RGBW strip.
+    ///
+    /// SK6812RGBW LEDs are wired the same as WS2812s (with slightly different
+    /// timings, see `Timing::sk6812()`), but take an extra white byte per LED. This isn't part of `SmartLedsWrite` (its
+    /// `Color` type is fixed to `RGB8`), so it's offered as a plain method
+    /// instead, reusing the same `write_byte`/`wait` timing as `write`.
+    #[allow(clippy::result_unit_err)]
+    pub fn write_rgbw<T, I>(&mut self, iterator: T) -> Result<(), ()>
+    where
+        T: Iterator<Item = I>,
+        I: Into<RGBW<u8>>,
+    {
+        for item in iterator {
+            let item = item.into();
+            let (b0, b1, b2) = self.color_order.reorder(RGB8 {
+                r: item.r,
+                g: item.g,
+                b: item.b,
+            });
+            self.write_byte(b0);
+            self.write_byte(b1);
+            self.write_byte(b2);
+            self.write_byte(item.a.0);
+        }
+
+        self.wait(n_loops_at(self.timing.reset_ns, self.frequency_mhz));
+        Ok(())
+    }
 }
 
 impl<PIN> SmartLedsWrite for Ws2812<PIN>
@@ -159,13 +345,247 @@ where
     {
         for item in iterator {
             let item = item.into();
-            self.write_byte(item.g);
-            self.write_byte(item.r);
-            self.write_byte(item.b);
+            let (b0, b1, b2) = self.color_order.reorder(item);
+            self.write_byte(b0);
+            self.write_byte(b1);
+            self.write_byte(b2);
+        }
+
+        self.wait(n_loops_at(self.timing.reset_ns, self.frequency_mhz));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "critical-section")]
+impl<PIN> Ws2812<PIN>
+where
+    PIN: OutputPin,
+{
+    /// Like [`SmartLedsWrite::write`], but masks interrupts for the whole
+    /// frame via [`critical_section::with`].
+    ///
+    /// This driver meets WS2812 timing purely with `nop` loops, so an
+    /// interrupt firing mid-transmission stretches a bit's high or low time
+    /// past the chip's tolerance and corrupts the frame. Reach for this
+    /// instead of `write` whenever interrupts on this core aren't otherwise
+    /// masked during the transmission and you can't tolerate the occasional
+    /// glitch.
+    #[allow(clippy::result_unit_err)]
+    pub fn write_blocking<T, I>(&mut self, iterator: T) -> Result<(), ()>
+    where
+        T: Iterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        critical_section::with(|_| self.write(iterator))
+    }
+}
+
+#[cfg(feature = "calibrate")]
+impl<PIN> Ws2812<PIN>
+where
+    PIN: OutputPin,
+{
+    /// Measure the fixed cost of toggling `PIN` through `OutputPin`, and bake
+    /// that overhead out of the loop counts used for each pulse width.
+    ///
+    /// `n_loops_at`/`wait` assume the `nop` loop is the only time spent per
+    /// pulse, but `pin.set_high()`/`set_low()` cost real cycles too, which
+    /// shifts the emitted pulse widths away from `self.timing` and is exactly
+    /// why strips sometimes show wrong colors at marginal timings. This uses
+    /// the Cortex-M `DWT` cycle counter to measure the cost of a
+    /// `set_high`/`set_low` pair (what `write_bit` calls once per bit),
+    /// splits that overhead evenly between the bit's high and low phases,
+    /// and stores the corrected loop counts so `write_bit` no longer has to
+    /// guess.
+    ///
+    /// `dwt` must already have its cycle counter enabled
+    /// (`dwt.enable_cycle_counter()`); it's only taken here so the caller
+    /// proves they have exclusive access to it.
+    pub fn calibrate(&mut self, _dwt: &mut DWT) {
+        let start = DWT::cycle_count();
+        self.pin.set_high().ok();
+        self.pin.set_low().ok();
+        let end = DWT::cycle_count();
+
+        // DWT counts core clock cycles; `self.frequency_mhz` is the same
+        // cycles-per-microsecond figure `n_loops_at` uses, so converting
+        // through it keeps the overhead in the same units as `self.timing`.
+        let toggle_cycles = end.wrapping_sub(start) as f32;
+        let overhead_ns = toggle_cycles / self.frequency_mhz * 1000.0;
+        // `set_high` delays the start of the high phase and `set_low` delays
+        // the start of the low phase, so each phase only pays about half of
+        // the measured pair's overhead.
+        let half_overhead_ns = overhead_ns / 2.0;
+
+        // Floor at 1 loop, not 0: `nop_wait` decrements its counter before
+        // testing it, so `nop_wait(0)` wraps to `u32::MAX` and hangs for
+        // seconds instead of executing a too-short (but harmless) pulse.
+        let corrected =
+            |ns: f32| n_loops_at((ns - half_overhead_ns).max(0.0), self.frequency_mhz).max(1);
+        self.calibrated = Some(CalibratedLoops {
+            t0h: corrected(self.timing.t0h_ns),
+            t0l: corrected(self.timing.t0l_ns),
+            t1h: corrected(self.timing.t1h_ns),
+            t1l: corrected(self.timing.t1l_ns),
+        });
+    }
+}
+
+/// Drive several WS2812 strips in parallel from a single `nop`-timed loop.
+///
+/// `Ws2812` toggles one `OutputPin` per bit, so driving `N` strips means `N`
+/// full-length frames back to back. `Ws2812Parallel` instead toggles a whole
+/// GPIO port mask per bit: on imxrt1062 that's the `GPIO{n}_DR_SET` and
+/// `GPIO{n}_DR_CLEAR` registers, which set or clear every pin named in a
+/// 32-bit mask with a single write. All strips on the same GPIO port refresh
+/// together in the time it'd otherwise take to refresh just one.
+///
+/// `PINS` is the number of strips (and so the number of pin masks) driven at
+/// once.
+pub struct Ws2812Parallel<const PINS: usize> {
+    /// `GPIO{n}_DR_SET`: writing a `1` bit here drives the corresponding pin high.
+    pub gpio_dr_set: *mut u32,
+    /// `GPIO{n}_DR_CLEAR`: writing a `1` bit here drives the corresponding pin low.
+    pub gpio_dr_clear: *mut u32,
+    /// One bit mask per strip, each with a single bit set for that strip's pin.
+    pub pins: [u32; PINS],
+    pub frequency_mhz: f32,
+    pub timing: Timing,
+    pub color_order: ColorOrder,
+}
+
+impl<const PINS: usize> Ws2812Parallel<PINS> {
+    /// `gpio_dr_set`/`gpio_dr_clear` must be the `DR_SET`/`DR_CLEAR` register
+    /// addresses of the GPIO port that every pin in `pins` belongs to, and
+    /// each entry of `pins` must have exactly one bit set.
+    ///
+    /// # Safety
+    /// The caller must guarantee `gpio_dr_set` and `gpio_dr_clear` are valid,
+    /// correctly-mapped MMIO addresses for as long as the returned value is
+    /// used.
+    pub unsafe fn new(
+        gpio_dr_set: *mut u32,
+        gpio_dr_clear: *mut u32,
+        pins: [u32; PINS],
+        frequency_mhz: f32,
+    ) -> Self {
+        Self::new_with_timing(
+            gpio_dr_set,
+            gpio_dr_clear,
+            pins,
+            frequency_mhz,
+            Timing::default(),
+        )
+    }
+
+    /// Like [`Ws2812Parallel::new`], but with an explicit [`Timing`].
+    ///
+    /// # Safety
+    /// Same requirements as [`Ws2812Parallel::new`].
+    pub unsafe fn new_with_timing(
+        gpio_dr_set: *mut u32,
+        gpio_dr_clear: *mut u32,
+        pins: [u32; PINS],
+        frequency_mhz: f32,
+        timing: Timing,
+    ) -> Self {
+        // Start every strip's data line low, same as `Ws2812::new`.
+        let all_pins = pins.iter().fold(0, |mask, pin| mask | pin);
+        core::ptr::write_volatile(gpio_dr_clear, all_pins);
+        Self {
+            gpio_dr_set,
+            gpio_dr_clear,
+            pins,
+            frequency_mhz,
+            timing,
+            color_order: ColorOrder::default(),
+        }
+    }
+
+    /// Set the wire color order, for strips that aren't GRB.
+    pub fn with_color_order(mut self, color_order: ColorOrder) -> Self {
+        self.color_order = color_order;
+        self
+    }
+
+    /// Wait for (ideally) `loops` * `CYCLES_PER_LOOP` core clock cycles.
+    #[inline(always)]
+    fn wait(&self, loops: i32) {
+        nop_wait(loops)
+    }
+
+    /// Drive one bit position across every strip at once.
+    ///
+    /// `active_mask` has a bit set for every strip still supplying data at
+    /// this position; `low_mask` (a subset of `active_mask`) has a bit set
+    /// for every strip whose bit at this position is `0`.
+    ///
+    /// The `0`-bit pins (`low_mask`) and `1`-bit pins (the rest of
+    /// `active_mask`) go low at different times but are driven from the same
+    /// two MMIO writes, so they can't each get their exact `t0l_ns`/`t1l_ns`
+    /// low time independently. Instead each group is held low for *at
+    /// least* its target: the `0`-bit pins go low after `t0h_ns` and stay
+    /// low until whichever is later of `t0l_ns` or the remaining time until
+    /// `t1h_ns`, then the `1`-bit pins go low and get their own `t1l_ns`.
+    /// This assumes `t1h_ns >= t0h_ns`, true for every `Timing` preset this
+    /// crate ships; for a `Timing` that violates it, the remaining time until
+    /// `t1h_ns` counts as zero, so the `1`-bit pins go low `t0l_ns` after the
+    /// `0`-bit pins and are held high for `t0h_ns + t0l_ns`.
+    fn write_bit_mask(&self, active_mask: u32, low_mask: u32) {
+        let high1_mask = active_mask & !low_mask;
+
+        let t0h = n_loops_at(self.timing.t0h_ns, self.frequency_mhz);
+        let t0l = n_loops_at(self.timing.t0l_ns, self.frequency_mhz);
+        let t1h_remaining = n_loops_at(
+            (self.timing.t1h_ns - self.timing.t0h_ns).max(0.0),
+            self.frequency_mhz,
+        );
+        let t1l = n_loops_at(self.timing.t1l_ns, self.frequency_mhz);
+
+        unsafe {
+            core::ptr::write_volatile(self.gpio_dr_set, active_mask);
+            self.wait(t0h);
+            core::ptr::write_volatile(self.gpio_dr_clear, low_mask);
+            self.wait(t0l.max(t1h_remaining));
+            core::ptr::write_volatile(self.gpio_dr_clear, high1_mask);
+            self.wait(t1l);
+        }
+    }
+
+    /// Write one `RGB8` color stream per strip, in lockstep.
+    ///
+    /// Strips of differing length are allowed: once a strip runs out of
+    /// LEDs, its pin is simply left out of the mask for the remaining bit
+    /// positions instead of being toggled.
+    #[allow(clippy::result_unit_err)]
+    pub fn write(&mut self, strips: [&[RGB8]; PINS]) -> Result<(), ()> {
+        let max_len = strips.iter().map(|strip| strip.len()).max().unwrap_or(0);
+
+        for i in 0..max_len {
+            for byte_idx in 0..3 {
+                for bit in (0..8).rev() {
+                    let mut active_mask = 0;
+                    let mut low_mask = 0;
+                    for (strip, &pin) in strips.iter().zip(self.pins.iter()) {
+                        if let Some(&color) = strip.get(i) {
+                            active_mask |= pin;
+                            let (b0, b1, b2) = self.color_order.reorder(color);
+                            let byte = match byte_idx {
+                                0 => b0,
+                                1 => b1,
+                                _ => b2,
+                            };
+                            if (byte >> bit) & 1 == 0 {
+                                low_mask |= pin;
+                            }
+                        }
+                    }
+                    self.write_bit_mask(active_mask, low_mask);
+                }
+            }
         }
 
-        // TODO: add feature cfg for setting wait time to 250us
-        self.wait(n_loops_at(6000.0, self.frequency_mhz));
+        self.wait(n_loops_at(self.timing.reset_ns, self.frequency_mhz));
         Ok(())
     }
 }